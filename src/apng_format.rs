@@ -0,0 +1,39 @@
+//! Animated PNG (APNG) output via the `png` crate; `image`'s `PngEncoder`
+//! only writes a single frame, which isn't enough for the flip animation.
+
+use std::fs::File;
+
+use image::Frame;
+use png::{BitDepth, ColorType, Encoder};
+
+pub fn encode(output: &mut File, frames: Vec<Frame>) -> Result<(), String> {
+    let first = frames
+        .first()
+        .ok_or_else(|| "no frames to encode".to_string())?;
+    let (width, height) = (first.buffer().width(), first.buffer().height());
+
+    let mut encoder = Encoder::new(&mut *output, width, height);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .map_err(|e| format!("failed to start APNG stream: {e}"))?;
+
+    let mut writer = encoder
+        .write_header()
+        .map_err(|e| format!("failed to write PNG header: {e}"))?;
+
+    for frame in &frames {
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        writer
+            .set_frame_delay((numer / denom.max(1)) as u16, 1000)
+            .map_err(|e| format!("failed to set APNG frame delay: {e}"))?;
+        writer
+            .write_image_data(frame.buffer().as_raw())
+            .map_err(|e| format!("failed to write APNG frame: {e}"))?;
+    }
+
+    writer
+        .finish()
+        .map_err(|e| format!("failed to finish APNG stream: {e}"))
+}
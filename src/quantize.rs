@@ -0,0 +1,164 @@
+//! Palette-size and dithering control for GIF output, on top of the
+//! `color_quant` crate that `image`'s own GIF encoder uses internally.
+//!
+//! `image::codecs::gif::GifEncoder` quantizes every frame itself with no way
+//! to choose the palette size or disable dithering, so when the user sets a
+//! palette size (`--colors`) we drop down to building indexed frames
+//! ourselves. `--no-dither` on its own does not select this path.
+
+use std::fs::File;
+
+use color_quant::NeuQuant;
+use gif::{Encoder, Frame as GifFrame, Repeat};
+use image::{Frame, RgbaImage};
+
+/// Quality passed to `NeuQuant`: lower is slower but builds a better palette.
+/// 10 is the quality `image`'s own gif encoder defaults to.
+const QUANT_QUALITY: i32 = 10;
+
+/// A pixel counts as transparent (and gets mapped to the dedicated
+/// transparent palette slot below) rather than quantized as a color.
+const TRANSPARENCY_THRESHOLD: u8 = 128;
+
+/// Quantizes `buf` to at most `colors` colors, returning the per-pixel
+/// palette indices, the RGB palette, and the index reserved for
+/// transparency, if `buf` has any transparent pixels.
+///
+/// Transparent pixels (the squash frames' side margins, see
+/// `build_flip_frames`) are excluded from the palette fit and from
+/// dithering's error diffusion so they don't pull colors toward black, and
+/// get their own reserved index instead of being quantized.
+fn quantize_frame(buf: &RgbaImage, colors: u16, dither: bool) -> (Vec<u8>, Vec<u8>, Option<u8>) {
+    let has_transparency = buf.pixels().any(|p| p.0[3] < TRANSPARENCY_THRESHOLD);
+
+    // leave a palette slot free for transparency so the total stays within
+    // what the caller asked for.
+    let opaque_colors = if has_transparency {
+        (colors.max(2) - 1).max(1)
+    } else {
+        colors
+    };
+
+    let opaque_pixels: Vec<u8> = buf
+        .pixels()
+        .filter(|p| p.0[3] >= TRANSPARENCY_THRESHOLD)
+        .flat_map(|p| p.0)
+        .collect();
+    let quant = NeuQuant::new(
+        QUANT_QUALITY,
+        opaque_colors as usize,
+        if opaque_pixels.is_empty() {
+            buf.as_raw()
+        } else {
+            &opaque_pixels
+        },
+    );
+    let mut palette = quant.color_map_rgb();
+
+    let transparent_index = has_transparency.then(|| {
+        let index = (palette.len() / 3) as u8;
+        palette.extend_from_slice(&[0, 0, 0]);
+        index
+    });
+
+    let (w, h) = (buf.width() as usize, buf.height() as usize);
+    let mut indices = Vec::with_capacity(w * h);
+
+    if dither {
+        // Floyd-Steinberg error diffusion over the quantizer's own palette.
+        let mut rgb: Vec<[f32; 3]> = buf
+            .pixels()
+            .map(|p| [p.0[0] as f32, p.0[1] as f32, p.0[2] as f32])
+            .collect();
+
+        for y in 0..h {
+            for x in 0..w {
+                let i = y * w + x;
+
+                if buf.get_pixel(x as u32, y as u32).0[3] < TRANSPARENCY_THRESHOLD {
+                    indices.push(transparent_index.expect("has_transparency implies a slot"));
+                    continue;
+                }
+
+                let [r, g, b] = rgb[i];
+                let pixel = [
+                    r.clamp(0.0, 255.0) as u8,
+                    g.clamp(0.0, 255.0) as u8,
+                    b.clamp(0.0, 255.0) as u8,
+                    255,
+                ];
+                let index = quant.index_of(&pixel);
+                indices.push(index as u8);
+
+                let quantized = &palette[index * 3..index * 3 + 3];
+                let err = [
+                    r - quantized[0] as f32,
+                    g - quantized[1] as f32,
+                    b - quantized[2] as f32,
+                ];
+
+                let mut spread = |x: i64, y: i64, factor: f32| {
+                    if x < 0
+                        || y < 0
+                        || x as usize >= w
+                        || y as usize >= h
+                        || buf.get_pixel(x as u32, y as u32).0[3] < TRANSPARENCY_THRESHOLD
+                    {
+                        return;
+                    }
+                    let j = y as usize * w + x as usize;
+                    for c in 0..3 {
+                        rgb[j][c] += err[c] * factor;
+                    }
+                };
+
+                spread(x as i64 + 1, y as i64, 7.0 / 16.0);
+                spread(x as i64 - 1, y as i64 + 1, 3.0 / 16.0);
+                spread(x as i64, y as i64 + 1, 5.0 / 16.0);
+                spread(x as i64 + 1, y as i64 + 1, 1.0 / 16.0);
+            }
+        }
+    } else {
+        for pixel in buf.pixels() {
+            if pixel.0[3] < TRANSPARENCY_THRESHOLD {
+                indices.push(transparent_index.expect("has_transparency implies a slot"));
+            } else {
+                indices.push(quant.index_of(&pixel.0) as u8);
+            }
+        }
+    }
+
+    (indices, palette, transparent_index)
+}
+
+/// Encodes `frames` as an indexed-color GIF quantized down to `colors`
+/// (2-256) colors, dithering the result when `dither` is set.
+pub fn encode(output: &mut File, frames: Vec<Frame>, colors: u16, dither: bool) -> Result<(), String> {
+    let Some(first) = frames.first() else {
+        return Ok(());
+    };
+    let (width, height) = (first.buffer().width() as u16, first.buffer().height() as u16);
+
+    let mut encoder = Encoder::new(output, width, height, &[])
+        .map_err(|e| format!("failed to start GIF stream: {e}"))?;
+    encoder.set_repeat(Repeat::Infinite).ok();
+
+    for frame in frames {
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        let delay_cs = (numer / denom.max(1) / 10) as u16;
+        let buf = frame.into_buffer();
+        let (w, h) = (buf.width() as u16, buf.height() as u16);
+
+        let (indices, palette, transparent_index) = quantize_frame(&buf, colors, dither);
+
+        let mut gif_frame = GifFrame::from_indexed_pixels(w, h, indices, transparent_index);
+        gif_frame.palette = Some(palette);
+        gif_frame.delay = delay_cs;
+
+        encoder
+            .write_frame(&gif_frame)
+            .map_err(|e| format!("failed to write GIF frame: {e}"))?;
+    }
+
+    Ok(())
+}
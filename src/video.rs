@@ -0,0 +1,187 @@
+//! Video frame extraction, gated behind the `video` Cargo feature.
+//!
+//! Decodes a video container frame-by-frame with `ffmpeg-next`, running each
+//! decoded frame through the same crop/resize pipeline as a still image
+//! before handing the sequence to the selected output encoder.
+
+use std::path::Path;
+use std::time::Duration;
+
+use ffmpeg_next as ffmpeg;
+use image::{Delay, DynamicImage, Frame, RgbaImage, imageops::FilterType};
+
+use crate::{CropRect, ResizeOp};
+
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "webm", "mov", "mkv"];
+
+/// Copies swscale's RGBA output plane into a tightly-packed `RgbaImage`.
+///
+/// ffmpeg pads each row to its own `stride`, which is usually wider than
+/// `width * 4` (alignment is commonly 32 or 64 bytes), so `data(0)` can't be
+/// handed to `RgbaImage::from_raw` directly — every row after the first
+/// would be read at the wrong offset.
+fn copy_rgba_plane(frame: &ffmpeg::util::frame::video::Video) -> RgbaImage {
+    let (width, height) = (frame.width(), frame.height());
+    let stride = frame.stride(0);
+    let data = frame.data(0);
+
+    let mut packed = Vec::with_capacity((width * height * 4) as usize);
+    let row_bytes = (width * 4) as usize;
+    for row in 0..height as usize {
+        let start = row * stride;
+        packed.extend_from_slice(&data[start..start + row_bytes]);
+    }
+
+    RgbaImage::from_raw(width, height, packed).expect("packed buffer matches width*height*4")
+}
+
+/// Whether `path` looks like a video container based on its extension.
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Decodes `path` into a sequence of animation frames, applying crop/resize and
+/// downsampling to `fps` (defaulting to the source frame rate), capped at
+/// `max_frames`.
+pub fn extract_frames(
+    path: &Path,
+    crop_rect: Option<&CropRect>,
+    crop: u32,
+    scale: f32,
+    resize: Option<&ResizeOp>,
+    filter: FilterType,
+    fps: Option<f32>,
+    max_frames: Option<u32>,
+) -> Result<Vec<Frame>, String> {
+    ffmpeg::init().map_err(|e| format!("failed to init ffmpeg: {e}"))?;
+
+    let mut ictx = ffmpeg::format::input(&path)
+        .map_err(|e| format!("failed to open video `{}`: {e}", path.display()))?;
+
+    let input = ictx
+        .streams()
+        .best(ffmpeg::media::Type::Video)
+        .ok_or_else(|| format!("no video stream in `{}`", path.display()))?;
+    let video_stream_index = input.index();
+
+    let source_fps = {
+        let rate = input.avg_frame_rate();
+        rate.numerator() as f32 / rate.denominator().max(1) as f32
+    };
+    let target_fps = fps.unwrap_or(source_fps).min(source_fps.max(1.0)).max(1.0);
+    let keep_every = (source_fps / target_fps).round().max(1.0) as u32;
+    let delay = Delay::from_saturating_duration(Duration::from_millis(
+        (1000.0 / target_fps) as u64,
+    ));
+
+    let context_decoder =
+        ffmpeg::codec::context::Context::from_parameters(input.parameters())
+            .map_err(|e| format!("failed to read codec parameters: {e}"))?;
+    let mut decoder = context_decoder
+        .decoder()
+        .video()
+        .map_err(|e| format!("failed to open video decoder: {e}"))?;
+
+    let mut scaler = ffmpeg::software::scaling::context::Context::get(
+        decoder.format(),
+        decoder.width(),
+        decoder.height(),
+        ffmpeg::format::Pixel::RGBA,
+        decoder.width(),
+        decoder.height(),
+        ffmpeg::software::scaling::flag::Flags::BILINEAR,
+    )
+    .map_err(|e| format!("failed to build scaler: {e}"))?;
+
+    let mut frames = Vec::new();
+    let mut decoded_index: u32 = 0;
+    let mut decoded = ffmpeg::util::frame::video::Video::empty();
+    let mut rgba = ffmpeg::util::frame::video::Video::empty();
+
+    // Applies the crop/resize pipeline to one decoded frame and appends it
+    // to `frames`. Returns whether `max_frames` has now been reached, so the
+    // caller can stop feeding the decoder more packets/EOF.
+    let mut process_decoded = |decoded: &ffmpeg::util::frame::video::Video,
+                                scaler: &mut ffmpeg::software::scaling::context::Context,
+                                rgba: &mut ffmpeg::util::frame::video::Video,
+                                frames: &mut Vec<Frame>,
+                                decoded_index: &mut u32|
+     -> Result<bool, String> {
+        let keep = *decoded_index % keep_every == 0;
+        *decoded_index += 1;
+
+        if !keep {
+            return Ok(false);
+        }
+
+        scaler
+            .run(decoded, rgba)
+            .map_err(|e| format!("failed to scale frame: {e}"))?;
+
+        let buf = copy_rgba_plane(rgba);
+        let mut image = DynamicImage::ImageRgba8(buf);
+
+        if let Some(rect) = crop_rect {
+            let (w, h) = (image.width(), image.height());
+            let (x, y, w, h) = rect.clamp(w, h);
+            image = image.crop(x, y, w, h);
+        }
+
+        if crop > 0 {
+            let (w, h) = (image.width(), image.height());
+            let crop_x = (2 * crop).min(w);
+            let crop_y = (2 * crop).min(h);
+            if w > crop_x && h > crop_y {
+                image = image.crop(crop, crop, w - crop_x, h - crop_y);
+            }
+        }
+
+        if let Some(op) = resize {
+            image = op.apply(&image, filter);
+        } else {
+            let (w, h) = (image.width(), image.height());
+            image = image.resize(
+                ((w as f32 * scale).round() as u32).max(2),
+                ((h as f32 * scale).round() as u32).max(2),
+                filter,
+            );
+        }
+
+        frames.push(Frame::from_parts(image.into_rgba8(), 0, 0, delay));
+
+        Ok(max_frames.is_some_and(|max| frames.len() as u32 >= max))
+    };
+
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != video_stream_index {
+            continue;
+        }
+
+        decoder
+            .send_packet(&packet)
+            .map_err(|e| format!("failed to decode frame: {e}"))?;
+
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            if process_decoded(&decoded, &mut scaler, &mut rgba, &mut frames, &mut decoded_index)? {
+                return Ok(frames);
+            }
+        }
+    }
+
+    // codecs with B-frames/reordering hold several frames in flight; flush
+    // the decoder so the tail of the video isn't silently dropped.
+    decoder
+        .send_eof()
+        .map_err(|e| format!("failed to flush decoder: {e}"))?;
+
+    while decoder.receive_frame(&mut decoded).is_ok() {
+        if process_decoded(&decoded, &mut scaler, &mut rgba, &mut frames, &mut decoded_index)? {
+            return Ok(frames);
+        }
+    }
+
+    Ok(frames)
+}
@@ -0,0 +1,37 @@
+//! Animated WebP output via the `webp` crate; `image`'s own `WebPEncoder`
+//! only supports a single frame, which isn't enough for the flip animation.
+
+use std::fs::File;
+use std::io::Write;
+
+use image::Frame;
+use webp::{AnimEncoder, AnimFrame, WebPConfig};
+
+pub fn encode(output: &mut File, frames: Vec<Frame>) -> Result<(), String> {
+    let first = frames
+        .first()
+        .ok_or_else(|| "no frames to encode".to_string())?;
+    let (width, height) = (first.buffer().width(), first.buffer().height());
+
+    let config = WebPConfig::new().map_err(|_| "failed to build WebP config".to_string())?;
+    let mut encoder = AnimEncoder::new(width, height, &config);
+
+    let mut timestamp_ms: i32 = 0;
+    for frame in &frames {
+        encoder.add_frame(AnimFrame::from_rgba(
+            frame.buffer().as_raw(),
+            width,
+            height,
+            timestamp_ms,
+        ));
+
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        timestamp_ms += (numer / denom.max(1)).max(1) as i32;
+    }
+
+    let webp = encoder.encode();
+
+    output
+        .write_all(&webp)
+        .map_err(|e| format!("failed to write WebP output: {e}"))
+}